@@ -13,8 +13,11 @@
 // limitations under the License.
 
 use std::env;
+use std::fmt;
+use std::mem;
+use std::cell::RefCell;
 use std::path::{PathBuf, Path};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use notify::DebouncedEvent;
 use config_rs::{self, Source, Value, FileFormat};
@@ -28,6 +31,16 @@ static XDG_CONFIG_HOME: &'static str = "XDG_CONFIG_HOME";
 /// A client can use this to pass a path to bundled plugins
 static XI_SYS_PLUGIN_PATH: &'static str = "XI_SYS_PLUGIN_PATH";
 static XI_CONFIG_FILE_NAME: &'static str = "preferences.xiconfig";
+/// Environment variables with this prefix override config keys, e.g.
+/// `XI_CONFIG_TAB_SIZE=2`. See `env_overrides_impl`.
+static XI_CONFIG_ENV_PREFIX: &'static str = "XI_CONFIG_";
+/// A config file's `imports` key lists other files to load first;
+/// values in the importing file take precedence over imported ones.
+static CONFIG_KEY_IMPORTS: &'static str = "imports";
+/// Import chains longer than this are rejected, as a backstop against
+/// cycles that the visited-set can't catch (such as a file reached
+/// again through a hard link, which canonicalizes to a different path).
+const IMPORT_RECURSION_LIMIT: usize = 5;
 
 /// Namespace for various default settings.
 #[allow(unused)]
@@ -38,14 +51,21 @@ mod defaults {
     pub const YAML: &'static str = include_str!("../assets/yaml.toml");
     pub const MAKEFILE: &'static str = include_str!("../assets/makefile.toml");
 
-    pub fn platform_defaults() -> Table {
+    /// Loads the base defaults, then overlays any platform-specific
+    /// overrides, returning the merged table along with the origin
+    /// (`Default` or `Platform`) of each resulting key.
+    pub fn platform_defaults() -> (Table, OriginTable) {
         let mut base = load(BASE);
+        let mut origins: OriginTable = base.keys()
+            .map(|k| (k.to_owned(), ConfigOrigin::Default))
+            .collect();
         if let Some(mut overrides) = platform_overrides() {
             for (k, v) in overrides.drain() {
-                base.insert(k, v);
+                base.insert(k.clone(), v);
+                origins.insert(k, ConfigOrigin::Platform);
             }
         }
-        base
+        (base, origins)
     }
 
     pub fn syntax_defaults() -> HashMap<SyntaxDefinition, Table>  {
@@ -70,6 +90,167 @@ mod defaults {
 
 pub type Table = HashMap<String, Value>;
 
+/// A map from config keys to the origin of the value currently
+/// associated with that key.
+pub type OriginTable = HashMap<String, ConfigOrigin>;
+
+/// Identifies where a particular resolved config value came from, so
+/// that a client can report e.g. `tab_size = 2 (from yaml.xiconfig)`
+/// and bug reports can show exactly which file set a key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConfigOrigin {
+    /// The hardcoded, built-in default.
+    Default,
+    /// A platform-specific default, such as `windows.toml`.
+    Platform,
+    /// A built-in default for a particular syntax, such as `yaml.toml`.
+    Syntax(SyntaxDefinition),
+    /// A value read from a file on disk, such as `preferences.xiconfig`.
+    UserFile(PathBuf),
+    /// A value taken from an `XI_CONFIG_`-prefixed environment variable.
+    EnvVar,
+    /// A session-only override set internally by xi-core for a buffer.
+    BufferOverride,
+    /// A session-only override set by the client via RPC.
+    RpcOverride,
+}
+
+/// A structured, reportable config error, used in place of a bare
+/// `Result<_, ()>` or a panic so the client can be told exactly what
+/// went wrong, e.g. in an `alert` notification, instead of the change
+/// being silently dropped or the core panicking.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConfigError {
+    /// `path` could not be parsed as TOML, or loading it would exceed
+    /// `IMPORT_RECURSION_LIMIT` or close an import cycle.
+    Parse { path: PathBuf, message: String },
+    /// A config file or override referenced a key that isn't in
+    /// `SCHEMA`.
+    UnknownKey(String),
+    /// A config file or override gave `key` a value that doesn't
+    /// match its `SCHEMA` type.
+    TypeMismatch { key: String, expected: ValueType, found: Value },
+    /// Two `*.xiconfig` files in the same directory resolved to the
+    /// same syntax; `first` is the one actually in effect.
+    AmbiguousSource { syntax: SyntaxDefinition, first: PathBuf, second: PathBuf },
+    /// An `XI_CONFIG_`-prefixed environment variable didn't match a
+    /// known key, or its value didn't parse as that key's schema type.
+    EnvVar { var: String, message: String },
+    /// A `*.xiconfig` file's name didn't match any known syntax.
+    UnrecognizedSyntax(String),
+    /// A reload or RPC referenced a config name that isn't
+    /// `"preferences"` or a known syntax.
+    UnknownConfigName(String),
+    /// The fully resolved config (every layer merged) failed to
+    /// convert into `Config`; should be unreachable, since each layer
+    /// is validated against `SCHEMA` before being merged in.
+    Resolve(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Parse { ref path, ref message } =>
+                write!(f, "error parsing config {:?}: {}", path, message),
+            ConfigError::UnknownKey(ref key) =>
+                write!(f, "unknown config key {:?}", key),
+            ConfigError::TypeMismatch { ref key, ref expected, ref found } =>
+                write!(f, "expected {} for key {:?}, found {:?}",
+                      expected.doc_hint(), key, found),
+            ConfigError::AmbiguousSource { ref syntax, ref first, ref second } =>
+                write!(f, "both {:?} and {:?} provide config for {:?}; using {:?}",
+                      first, second, syntax, first),
+            ConfigError::EnvVar { ref var, ref message } =>
+                write!(f, "error applying env var {}: {}", var, message),
+            ConfigError::UnrecognizedSyntax(ref name) =>
+                write!(f, "unrecognized syntax name {:?}", name),
+            ConfigError::UnknownConfigName(ref name) =>
+                write!(f, "unknown config name {:?}", name),
+            ConfigError::Resolve(ref message) =>
+                write!(f, "error applying resolved config: {}", message),
+        }
+    }
+}
+
+/// The primitive shape of a setting's value, used both to document a
+/// setting and to validate values coming from user files or RPCs,
+/// rather than letting a bad value panic in `Value::try_into`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ValueType {
+    Bool,
+    UInt,
+    Str,
+    StrList,
+}
+
+impl ValueType {
+    /// A short, human readable hint describing this type, e.g.
+    /// `<unsigned integer>`.
+    pub fn doc_hint(&self) -> &'static str {
+        match *self {
+            ValueType::Bool => "true | false",
+            ValueType::UInt => "<unsigned integer>",
+            ValueType::Str => "<string>",
+            ValueType::StrList => "<list of strings>",
+        }
+    }
+
+    fn matches(&self, value: &Value) -> bool {
+        match *self {
+            ValueType::Bool => value.clone().try_into::<bool>().is_ok(),
+            ValueType::UInt => value.clone().try_into::<u64>().is_ok(),
+            ValueType::Str => value.clone().try_into::<String>().is_ok(),
+            ValueType::StrList => value.clone().try_into::<Vec<String>>().is_ok(),
+        }
+    }
+}
+
+/// Describes a single user-modifiable setting, so a client can build a
+/// settings panel, or validate a value, without reading source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingDescriptor {
+    pub name: &'static str,
+    pub value_type: ValueType,
+    /// The setting's built-in default, if one is currently loaded.
+    pub default: Option<Value>,
+    pub description: &'static str,
+}
+
+/// The schema for every user-modifiable setting in `Config`. Incoming
+/// user-file and RPC values are validated against this before being
+/// merged in, so an unknown key or a type mismatch is reported as a
+/// descriptive error instead of panicking deep in `get_config`.
+static SCHEMA: &'static [(&'static str, ValueType, &'static str)] = &[
+    ("newline", ValueType::Str,
+     "The characters inserted when a newline is created."),
+    ("tab_size", ValueType::UInt,
+     "The visual width, in columns, of a tab character."),
+    ("translate_tabs_to_spaces", ValueType::Bool,
+     "Whether indentation should be performed with spaces."),
+    ("plugin_search_path", ValueType::StrList,
+     "Additional paths to search for plugins."),
+];
+
+/// Checks `table` against `SCHEMA`, returning a descriptive error for
+/// the first unknown key or type mismatch found.
+fn validate_table(table: &Table) -> Result<(), ConfigError> {
+    for (key, value) in table.iter() {
+        match SCHEMA.iter().find(|&&(name, _, _)| name == key) {
+            None => return Err(ConfigError::UnknownKey(key.to_owned())),
+            Some(&(_, ref value_type, _)) => {
+                if !value_type.matches(value) {
+                    return Err(ConfigError::TypeMismatch {
+                        key: key.to_owned(),
+                        expected: value_type.clone(),
+                        found: value.clone(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Represents the common pattern of default settings masked by
 /// user settings.
 #[derive(Debug, Clone, Default)]
@@ -81,6 +262,12 @@ pub struct ConfigPair {
     user: Option<Table>,
     /// A snapshot of base + user.
     cache: Table,
+    /// The origin of each key currently in `base`.
+    base_origins: OriginTable,
+    /// The origin of each key currently in `user`.
+    user_origins: OriginTable,
+    /// A snapshot of the origin of each key in `cache`.
+    origins: OriginTable,
 }
 
 #[derive(Debug)]
@@ -97,6 +284,48 @@ pub struct ConfigManager {
     /// An optional client-provided path for bundled resources, such
     /// as plugins and themes.
     extras_dir: Option<PathBuf>,
+    /// Maps each file that contributes to a config, directly or via an
+    /// `imports` directive, to the root config name(s) (`"preferences"`,
+    /// or a syntax name) that should be reloaded when it changes.
+    import_graph: HashMap<PathBuf, Vec<String>>,
+    /// Config keys sourced from `XI_CONFIG_*` environment variables.
+    /// Layered above user files but below buffer/RPC overrides; see
+    /// `resolve`.
+    env_overrides: Table,
+    env_origins: OriginTable,
+    /// Per-buffer config discovered by ascending from the buffer's
+    /// file, set by `set_buffer_path`. See `LocalConfig`.
+    local_configs: HashMap<BufferIdentifier, LocalConfig>,
+    /// Maps each file discovered while ascending from a buffer's path
+    /// to the buffer(s) whose `local_configs` entry it contributed to,
+    /// so a change to it re-runs discovery for those buffers.
+    ancestor_graph: HashMap<PathBuf, Vec<BufferIdentifier>>,
+    /// Errors from loading or applying config, queued here rather than
+    /// printed directly so the owning component can forward them to
+    /// the client (e.g. via an `alert` notification). Drained by
+    /// `take_errors`. A `RefCell` so read-only paths like `get_config`
+    /// can still queue an error, via `queue_error`.
+    pending_errors: RefCell<Vec<ConfigError>>,
+}
+
+/// The config layered in from `preferences.xiconfig` (and any
+/// syntax-specific `*.xiconfig` files) found while ascending from a
+/// buffer's directory to the filesystem root, editorconfig-style.
+/// Sits between the global user config and that buffer's own
+/// overrides; the closest directory to the buffer wins.
+#[derive(Debug, Clone, Default)]
+struct LocalConfig {
+    /// The file the chain was discovered from, so it can be
+    /// re-discovered when one of `dirs` changes.
+    buf_path: PathBuf,
+    /// The merged ancestor chain for `preferences.xiconfig`.
+    defaults: ConfigPair,
+    /// The merged ancestor chain for each syntax-specific file.
+    syntax_specific: HashMap<SyntaxDefinition, ConfigPair>,
+    /// Every file that contributed to `defaults` or `syntax_specific`,
+    /// directly or via `imports`, recorded in `ancestor_graph` for
+    /// cache invalidation.
+    dirs: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,61 +337,110 @@ pub struct Config {
     pub plugin_search_path: Vec<PathBuf>,
 }
 
+/// Tags every key in `table` with `origin`.
+fn uniform_origins(table: &Table, origin: ConfigOrigin) -> OriginTable {
+    table.keys().map(|k| (k.to_owned(), origin.clone())).collect()
+}
+
 impl ConfigPair {
-    fn new<T1, T2>(base: T1, user: T2) -> Self
+    fn new<T1, T2>(base: T1, base_origin: ConfigOrigin, user: T2, user_origin: ConfigOrigin) -> Self
         where T1: Into<Option<Table>>,
               T2: Into<Option<Table>>,
     {
         let base = base.into();
         let user = user.into();
-        let cache = Table::new();
-        let mut conf = ConfigPair { base, user, cache };
+        let base_origins = base.as_ref()
+            .map(|t| uniform_origins(t, base_origin))
+            .unwrap_or_default();
+        let user_origins = user.as_ref()
+            .map(|t| uniform_origins(t, user_origin))
+            .unwrap_or_default();
+        let mut conf = ConfigPair {
+            base, user, cache: Table::new(),
+            base_origins, user_origins, origins: OriginTable::new(),
+        };
+        conf.rebuild();
+        conf
+    }
+
+    /// Like `new`, but allows `base` to carry a non-uniform origin per
+    /// key, as is the case for the platform-layered defaults table.
+    fn with_base_origins(base: Table, base_origins: OriginTable) -> Self {
+        let mut conf = ConfigPair {
+            base: Some(base), user: None, cache: Table::new(),
+            base_origins, user_origins: OriginTable::new(), origins: OriginTable::new(),
+        };
         conf.rebuild();
         conf
     }
 
-    fn set_user(&mut self, user: Table) {
+    fn set_user(&mut self, user: Table, origins: OriginTable) {
+        self.user_origins = origins;
         self.user = Some(user);
         self.rebuild();
     }
 
     fn rebuild(&mut self) {
         let mut cache = self.base.clone().unwrap_or_default();
+        let mut origins = self.base_origins.clone();
         if let Some(ref user) = self.user {
             for (k, v) in user.iter() {
                 cache.insert(k.to_owned(), v.clone());
             }
+            merge_origins(&mut origins, &self.user_origins);
         }
         self.cache = cache;
+        self.origins = origins;
     }
 
-    /// Manually sets a key/value pair in one of `base` or `user`.
+    /// Manually sets a key/value pair in one of `base` or `user`, along
+    /// with its origin. Rejects unknown keys and type-mismatched values
+    /// rather than admitting them silently.
     ///
     /// Note: this is only intended to be used internally, when handling
     /// overrides.
-    fn set_override<K, V>(&mut self, key: K, value: V, from_user: bool)
+    fn set_override<K, V>(&mut self, key: K, value: V, from_user: bool) -> Result<(), ConfigError>
         where K: AsRef<str>,
               V: Into<Value>,
     {
         let key: String = key.as_ref().to_owned();
         let value = value.into();
+        match SCHEMA.iter().find(|&&(name, _, _)| name == key) {
+            None => return Err(ConfigError::UnknownKey(key)),
+            Some(&(_, ref value_type, _)) => {
+                if !value_type.matches(&value) {
+                    return Err(ConfigError::TypeMismatch {
+                        key,
+                        expected: value_type.clone(),
+                        found: value,
+                    });
+                }
+            }
+        }
+        let origin = if from_user { ConfigOrigin::RpcOverride } else { ConfigOrigin::BufferOverride };
         {
-            let table = if from_user {
-                self.user.get_or_insert(Table::new())
+            let (table, origins) = if from_user {
+                (self.user.get_or_insert_with(Table::new), &mut self.user_origins)
             } else {
-                self.base.get_or_insert(Table::new())
+                (self.base.get_or_insert_with(Table::new), &mut self.base_origins)
             };
-            table.insert(key, value);
+            table.insert(key.clone(), value);
+            origins.insert(key, origin);
         }
         self.rebuild();
+        Ok(())
     }
 
-    /// Returns a new `Table`, with the values of `other`
-    /// inserted into a copy of `self.cache`.
-    fn merged_with(&self, other: &ConfigPair) -> Table {
+    /// Returns a new `Table` and `OriginTable`, with the values of
+    /// `other` inserted into copies of `self.cache` and `self.origins`.
+    /// Both are updated atomically, so the winning layer is always
+    /// reported correctly.
+    fn merged_with(&self, other: &ConfigPair) -> (Table, OriginTable) {
         let mut result = self.cache.clone();
+        let mut origins = self.origins.clone();
         merge_tables(&mut result, &other.cache);
-        result
+        merge_origins(&mut origins, &other.origins);
+        (result, origins)
     }
 }
 
@@ -171,32 +449,41 @@ impl ConfigManager {
     pub fn set_config_dir<P: AsRef<Path>>(&mut self, path: P) {
         let config_dir = path.as_ref().to_owned();
         let user_config_path = config_dir.join(XI_CONFIG_FILE_NAME);
-        let user_config = load_config(&user_config_path).unwrap_or_default();
-        let syntax_specific = load_syntax_configs(&config_dir);
+        let (user_config, user_origins, user_files, mut errors) = load_config(&user_config_path);
+        let (syntax_specific, syntax_errors) = load_syntax_configs(&config_dir);
+        errors.extend(syntax_errors);
+        self.queue_errors(errors);
         self.config_dir = Some(config_dir);
-        self.set_user_configs(Some(user_config), Some(syntax_specific));
+        self.set_user_configs(Some((user_config, user_origins, user_files, user_config_path)),
+                              Some(syntax_specific));
     }
 
     pub fn set_extras_dir<P: AsRef<Path>>(&mut self, path: P) {
         self.extras_dir = Some(path.as_ref().to_owned())
     }
 
-    /// Bulk apply initial user configs.
-    fn set_user_configs(&mut self, defaults: Option<Table>,
-                        syntax: Option<HashMap<SyntaxDefinition, Table>>) {
+    /// Bulk apply initial user configs. `files` lists every file that
+    /// contributed to `defaults`/each syntax config, directly or via
+    /// `imports`, and is recorded in `import_graph` so a later change to
+    /// any of them triggers a reload of the right root config.
+    fn set_user_configs(&mut self, defaults: Option<(Table, OriginTable, Vec<PathBuf>, PathBuf)>,
+                        syntax: Option<HashMap<SyntaxDefinition, (Table, OriginTable, String, Vec<PathBuf>, PathBuf)>>) {
         if let Some(mut syntax_settings) = syntax {
-            for (syntax, config) in syntax_settings.drain() {
-                self.set_user_syntax(syntax, config);
+            for (syntax, (config, origins, name, files, _path)) in syntax_settings.drain() {
+                self.update_import_graph(&name, &files);
+                self.set_user_syntax(syntax, config, origins);
             }
         }
 
-        if let Some(defaults) = defaults {
-            self.defaults.set_user(defaults);
+        if let Some((defaults, origins, files, _path)) = defaults {
+            self.update_import_graph("preferences", &files);
+            self.defaults.set_user(defaults, origins);
         }
     }
 
     /// Handle a file system event in `self.config_dir`; mostly this
-    /// means reload a changed configuration.
+    /// means reload a changed configuration, along with any config
+    /// that transitively `imports` it.
     pub fn handle_fs_event(&mut self, event: DebouncedEvent) {
         use self::DebouncedEvent::*;
         match event {
@@ -205,12 +492,7 @@ impl ConfigManager {
                     .and_then(|s| s.to_str())
                     .unwrap_or("");
                 if ext == "xiconfig" {
-                    let file_stem = path.file_stem().unwrap().to_string_lossy();
-                    match load_config(path) {
-                        Ok(config) => self.update_config(&file_stem, config),
-                        Err(e) => eprintln!("error parsing config at path {:?} \
-                                            error:\n{:?}", path, e),
-                    }
+                    self.reload_for_path(path);
                 }
             }
             //other => eprintln!("other config fs event:;\n{:?}", &other),
@@ -218,45 +500,235 @@ impl ConfigManager {
         }
     }
 
+    /// Reloads whichever root config(s) and local configs are affected
+    /// by a change to `path`. If `path` contributes to one or more
+    /// buffers' local config (see `set_buffer_path`), those are
+    /// re-discovered. If `path` is itself a root config
+    /// (`preferences.xiconfig` or a syntax file), it's reloaded
+    /// directly; if it's a file that one or more root configs
+    /// `imports`, each of those is reloaded instead.
+    fn reload_for_path(&mut self, path: &Path) {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        let mut handled = false;
+
+        if let Some(buf_ids) = self.ancestor_graph.get(&canonical).cloned() {
+            handled = true;
+            for buf_id in buf_ids {
+                let buf_path = self.local_configs.get(&buf_id).map(|l| l.buf_path.clone());
+                if let Some(buf_path) = buf_path {
+                    self.set_buffer_path(buf_id, buf_path);
+                }
+            }
+        }
+
+        if let Some(dependents) = self.import_graph.get(&canonical).cloned() {
+            handled = true;
+            for config_name in dependents {
+                self.reload_root_config(&config_name);
+            }
+        }
+
+        if !handled {
+            let file_stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+            self.reload_root_config(&file_stem);
+        }
+    }
+
+    /// Reloads the root config named `config_name` (`"preferences"`, or
+    /// a syntax name) from disk, re-resolving its `imports`. If the
+    /// reload fails, the errors are queued (see `take_errors`) and the
+    /// previously active config is left in place, so a typo in a
+    /// live-reloaded file never corrupts the running session.
+    fn reload_root_config(&mut self, config_name: &str) {
+        let config_dir = match self.config_dir {
+            Some(ref dir) => dir.clone(),
+            None => return,
+        };
+        let root_path = if config_name == "preferences" {
+            config_dir.join(XI_CONFIG_FILE_NAME)
+        } else if SyntaxDefinition::try_from_name(config_name).is_some() {
+            config_dir.join(format!("{}.xiconfig", config_name))
+        } else {
+            self.queue_error(ConfigError::UnknownConfigName(config_name.to_owned()));
+            return;
+        };
+        let (table, origins, files, errors) = load_config(&root_path);
+        self.update_import_graph(config_name, &files);
+        if !errors.is_empty() {
+            self.queue_errors(errors);
+            return;
+        }
+        self.update_config(config_name, table, origins);
+    }
+
     /// Replace the user config with the given name with a new config.
-    fn update_config(&mut self, config_name: &str, new_config: Table) {
+    fn update_config(&mut self, config_name: &str, new_config: Table, origins: OriginTable) {
         if config_name == "preferences" {
-            self.defaults.set_user(new_config);
+            self.defaults.set_user(new_config, origins);
         } else if let Some(s) = SyntaxDefinition::try_from_name(config_name) {
-            self.set_user_syntax(s, new_config);
+            self.set_user_syntax(s, new_config, origins);
         } else {
-            eprintln!("Unknown config name {}", config_name);
+            self.queue_error(ConfigError::UnknownConfigName(config_name.to_owned()));
         }
     }
 
-    fn set_user_syntax(&mut self, syntax: SyntaxDefinition, config: Table) {
+    /// Records that each of `files` contributes, directly or via
+    /// `imports`, to the root config named `config_name`.
+    fn update_import_graph(&mut self, config_name: &str, files: &[PathBuf]) {
+        for file in files {
+            let canonical = file.canonicalize().unwrap_or_else(|_| file.to_owned());
+            let dependents = self.import_graph.entry(canonical).or_insert_with(Vec::new);
+            if !dependents.iter().any(|n| n == config_name) {
+                dependents.push(config_name.to_owned());
+            }
+        }
+    }
+
+    fn set_user_syntax(&mut self, syntax: SyntaxDefinition, config: Table, origins: OriginTable) {
         let exists = self.syntax_specific.contains_key(&syntax);
         if exists {
             let syntax_pair = self.syntax_specific.get_mut(&syntax).unwrap();
-            syntax_pair.set_user(config);
+            syntax_pair.set_user(config, origins);
         } else {
-            let syntax_pair = ConfigPair::new(None, config);
+            let mut syntax_pair = ConfigPair::default();
+            syntax_pair.set_user(config, origins);
             self.syntax_specific.insert(syntax, syntax_pair);
         }
     }
 
-    /// Generates a snapshot of the current configuration for `syntax`.
-    pub fn get_config<S, V>(&self, syntax: S, buf_id: V) -> Config
+    /// Discovers `preferences.xiconfig` (and any syntax-specific
+    /// `*.xiconfig` files) in `buf_path`'s directory and each of its
+    /// ancestors, and layers them in as `buf_id`'s local config,
+    /// editorconfig-style. Call this whenever a buffer backed by a
+    /// file on disk is opened, so a repository can ship its own
+    /// settings that apply only to buffers inside it.
+    pub fn set_buffer_path<P: AsRef<Path>>(&mut self, buf_id: BufferIdentifier, buf_path: P) {
+        let buf_path = buf_path.as_ref().to_owned();
+        let mut local = self.discover_local_config(&buf_path);
+        local.buf_path = buf_path;
+        self.update_ancestor_graph(&buf_id, &local.dirs);
+        self.local_configs.insert(buf_id, local);
+    }
+
+    /// Builds a `LocalConfig` by ascending from `buf_path`'s directory
+    /// to the filesystem root, merging any `preferences.xiconfig` and
+    /// syntax-specific files found along the way. Directories closer
+    /// to `buf_path` take precedence over farther ones. Any errors
+    /// encountered are queued on `self.pending_errors` (see
+    /// `take_errors`); the files responsible are simply skipped.
+    fn discover_local_config(&mut self, buf_path: &Path) -> LocalConfig {
+        let mut ancestors: Vec<PathBuf> = match buf_path.parent() {
+            Some(dir) => dir.ancestors().map(|a| a.to_owned()).collect(),
+            None => Vec::new(),
+        };
+        ancestors.reverse();
+
+        let mut defaults_table = Table::new();
+        let mut defaults_origins = OriginTable::new();
+        let mut syntax_tables: HashMap<SyntaxDefinition, (Table, OriginTable)> = HashMap::new();
+        let mut dirs = Vec::new();
+
+        for dir in &ancestors {
+            let pref_path = dir.join(XI_CONFIG_FILE_NAME);
+            if pref_path.is_file() {
+                let (table, origins, files, errors) = load_config(&pref_path);
+                merge_tables(&mut defaults_table, &table);
+                merge_origins(&mut defaults_origins, &origins);
+                dirs.extend(files);
+                self.queue_errors(errors);
+            }
+
+            let (syntax_configs, syntax_errors) = load_syntax_configs(dir);
+            self.queue_errors(syntax_errors);
+            for (syntax, (table, origins, _name, files, _path)) in syntax_configs {
+                let entry = syntax_tables.entry(syntax).or_insert_with(Default::default);
+                merge_tables(&mut entry.0, &table);
+                merge_origins(&mut entry.1, &origins);
+                dirs.extend(files);
+            }
+        }
+
+        LocalConfig {
+            buf_path: PathBuf::new(),
+            defaults: ConfigPair::with_base_origins(defaults_table, defaults_origins),
+            syntax_specific: syntax_tables.into_iter()
+                .map(|(k, (t, o))| (k, ConfigPair::with_base_origins(t, o)))
+                .collect(),
+            dirs,
+        }
+    }
+
+    /// Records that each of `files` contributed, directly or via
+    /// `imports`, to `buf_id`'s local config, so a later change to any
+    /// of them re-runs discovery for that buffer.
+    fn update_ancestor_graph(&mut self, buf_id: &BufferIdentifier, files: &[PathBuf]) {
+        for file in files {
+            let canonical = file.canonicalize().unwrap_or_else(|_| file.to_owned());
+            let dependents = self.ancestor_graph.entry(canonical).or_insert_with(Vec::new);
+            if !dependents.iter().any(|b| b == buf_id) {
+                dependents.push(buf_id.to_owned());
+            }
+        }
+    }
+
+    /// Resolves the merged `Table` and `OriginTable` for `syntax` and
+    /// `buf_id`, without converting into a `Config`.
+    ///
+    /// Precedence, lowest to highest: built-in defaults, platform
+    /// defaults, the user's `preferences.xiconfig`, syntax defaults,
+    /// the user's syntax-specific file (user files including their
+    /// `imports`), `buf_id`'s local config (discovered by
+    /// `set_buffer_path`, closest ancestor directory wins),
+    /// `XI_CONFIG_*` environment variables, then session-only
+    /// buffer/RPC overrides.
+    fn resolve<S, V>(&self, syntax: S, buf_id: V) -> (Table, OriginTable)
         where S: Into<Option<SyntaxDefinition>>,
               V: Into<Option<BufferIdentifier>>
     {
         let syntax = syntax.into().unwrap_or_default();
         let buf_id = buf_id.into();
-        let mut settings = match self.syntax_specific.get(&syntax) {
+        let (mut settings, mut origins) = match self.syntax_specific.get(&syntax) {
             Some(ref syntax_config) => self.defaults.merged_with(syntax_config),
-            None => self.defaults.cache.clone(),
+            None => (self.defaults.cache.clone(), self.defaults.origins.clone()),
         };
 
+        if let Some(local) = buf_id.clone().and_then(|v| self.local_configs.get(&v)) {
+            let (local_table, local_origins) = match local.syntax_specific.get(&syntax) {
+                Some(ref syntax_config) => local.defaults.merged_with(syntax_config),
+                None => (local.defaults.cache.clone(), local.defaults.origins.clone()),
+            };
+            merge_tables(&mut settings, &local_table);
+            merge_origins(&mut origins, &local_origins);
+        }
+
+        merge_tables(&mut settings, &self.env_overrides);
+        merge_origins(&mut origins, &self.env_origins);
+
         if let Some(overrides) = buf_id.and_then(|v| self.overrides.get(&v)) {
             merge_tables(&mut settings, &overrides.cache);
+            merge_origins(&mut origins, &overrides.origins);
         }
-        let settings: Value = settings.into();
-        let mut settings: Config = settings.try_into().unwrap();
+        (settings, origins)
+    }
+
+    /// Generates a snapshot of the current configuration for `syntax`.
+    pub fn get_config<S, V>(&self, syntax: S, buf_id: V) -> Config
+        where S: Into<Option<SyntaxDefinition>>,
+              V: Into<Option<BufferIdentifier>>
+    {
+        let (settings, _origins) = self.resolve(syntax, buf_id);
+        let value: Value = settings.into();
+        let mut settings: Config = value.try_into().unwrap_or_else(|e| {
+            // every layer is validated against `SCHEMA` before it's
+            // merged in, so this should be unreachable; if it somehow
+            // isn't, queue it for the client and fall back to the
+            // built-in defaults rather than panicking the core on a
+            // malformed resolved config.
+            self.queue_error(ConfigError::Resolve(e.to_string()));
+            let base = self.defaults.base.clone().unwrap_or_default();
+            Value::from(base).try_into()
+                .expect("built-in default config must be valid")
+        });
         // relative entries in plugin search path should be relative to
         // the config directory.
         if let Some(ref config_dir) = self.config_dir {
@@ -272,33 +744,93 @@ impl ConfigManager {
         settings
     }
 
+    /// Like `get_config`, but reports the origin of each resolved value,
+    /// so a client can display e.g. `tab_size = 2 (from yaml.xiconfig)`.
+    pub fn get_config_with_origins<S, V>(&self, syntax: S, buf_id: V)
+        -> HashMap<String, (Value, ConfigOrigin)>
+        where S: Into<Option<SyntaxDefinition>>,
+              V: Into<Option<BufferIdentifier>>
+    {
+        let (settings, origins) = self.resolve(syntax, buf_id);
+        settings.into_iter()
+            .map(|(k, v)| {
+                let origin = origins.get(&k).cloned().unwrap_or(ConfigOrigin::Default);
+                (k, (v, origin))
+            })
+            .collect()
+    }
+
     /// Sets a session-specific, buffer-specific override. The `from_user`
     /// flag indicates whether this override is coming via RPC (true) or
-    /// from xi-core (false).
+    /// from xi-core (false). Returns an error, rather than panicking,
+    /// if `key` is unknown or `value` doesn't match its schema type.
     pub fn set_override<K, V>(&mut self, key: K, value: V,
-                              buf_id: BufferIdentifier, from_user: bool)
+                              buf_id: BufferIdentifier, from_user: bool) -> Result<(), ConfigError>
         where K: AsRef<str>,
               V: Into<Value>,
     {
         if !self.overrides.contains_key(&buf_id) {
-            let conf_pair = ConfigPair::new(None, None);
+            let conf_pair = ConfigPair::new(None, ConfigOrigin::Default, None, ConfigOrigin::Default);
             self.overrides.insert(buf_id.to_owned(), conf_pair);
         }
         self.overrides.get_mut(&buf_id)
             .unwrap()
-            .set_override(key, value, from_user);
+            .set_override(key, value, from_user)
+    }
+
+    /// Drains every `ConfigError` queued since the last call, so the
+    /// owning component can forward them to the client (e.g. as an
+    /// `alert` notification) instead of them being printed to stderr
+    /// or silently discarded.
+    pub fn take_errors(&mut self) -> Vec<ConfigError> {
+        mem::replace(&mut *self.pending_errors.borrow_mut(), Vec::new())
+    }
+
+    /// Queues `error` to be drained by `take_errors`. Takes `&self`
+    /// rather than `&mut self`, since some callers (e.g. `get_config`)
+    /// only have read access to the manager.
+    fn queue_error(&self, error: ConfigError) {
+        self.pending_errors.borrow_mut().push(error);
+    }
+
+    /// Like `queue_error`, for a batch of errors.
+    fn queue_errors(&self, errors: Vec<ConfigError>) {
+        self.pending_errors.borrow_mut().extend(errors);
+    }
+
+    /// Returns a descriptor for every known setting, so a client can
+    /// build a settings panel dynamically instead of hardcoding keys.
+    /// This is the data side of a `settings_schema`-style RPC request;
+    /// the request/response plumbing lives alongside the rest of the
+    /// core's RPC dispatch, not in this module.
+    pub fn available_settings(&self) -> Vec<SettingDescriptor> {
+        SCHEMA.iter()
+            .map(|&(name, ref value_type, description)| {
+                SettingDescriptor {
+                    name,
+                    value_type: value_type.clone(),
+                    default: self.defaults.base.as_ref().and_then(|b| b.get(name).cloned()),
+                    description,
+                }
+            })
+            .collect()
     }
 }
 
 impl Default for ConfigManager {
     fn default() -> ConfigManager {
-        let defaults = ConfigPair::new(defaults::platform_defaults(), None);
+        let (base, base_origins) = defaults::platform_defaults();
+        let defaults = ConfigPair::with_base_origins(base, base_origins);
         let mut syntax_specific = defaults::syntax_defaults();
         let syntax_specific = syntax_specific
             .drain()
-            .map(|(k, v)| {(k.to_owned(), ConfigPair::new(v, None)) })
+            .map(|(k, v)| {
+                let origin = ConfigOrigin::Syntax(k.to_owned());
+                (k.to_owned(), ConfigPair::new(v, origin, None, ConfigOrigin::Default))
+            })
             .collect::<HashMap<_, _>>();
         let extras_dir = env::var(XI_SYS_PLUGIN_PATH).map(PathBuf::from).ok();
+        let (env_overrides, env_origins, env_errors) = env_overrides();
 
         ConfigManager {
             defaults: defaults,
@@ -306,19 +838,190 @@ impl Default for ConfigManager {
             overrides: HashMap::new(),
             config_dir: None,
             extras_dir: extras_dir,
+            import_graph: HashMap::new(),
+            env_overrides: env_overrides,
+            env_origins: env_origins,
+            local_configs: HashMap::new(),
+            ancestor_graph: HashMap::new(),
+            pending_errors: RefCell::new(env_errors),
         }
     }
 }
 
-fn load_config(path: &Path) -> Result<Table, ()> {
+/// Loads `path` as a config file, resolving any `imports` directive
+/// transitively. Returns the merged table (empty, or missing whatever
+/// failed to load, if any `ConfigError`s are returned), the origin of
+/// each resulting key (a key pulled in via `imports` keeps the
+/// imported file as its origin, not the importing one), and every
+/// file that contributed, including `path` itself, so a caller can
+/// watch them for changes.
+fn load_config(path: &Path) -> (Table, OriginTable, Vec<PathBuf>, Vec<ConfigError>) {
+    let mut visited = HashSet::new();
+    load_config_rec(path, &mut visited, 0)
+}
+
+fn load_config_rec(path: &Path, visited: &mut HashSet<PathBuf>, depth: usize)
+    -> (Table, OriginTable, Vec<PathBuf>, Vec<ConfigError>)
+{
+    let mut files = vec![path.to_owned()];
+    if depth > IMPORT_RECURSION_LIMIT {
+        let message = format!("import recursion limit ({}) exceeded",
+                              IMPORT_RECURSION_LIMIT);
+        let error = ConfigError::Parse { path: path.to_owned(), message };
+        return (Table::new(), OriginTable::new(), files, vec![error]);
+    }
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    if !visited.insert(canonical.clone()) {
+        let error = ConfigError::Parse {
+            path: path.to_owned(),
+            message: "import cycle detected".to_owned(),
+        };
+        return (Table::new(), OriginTable::new(), files, vec![error]);
+    }
+
+    let mut table = match load_config_raw(path) {
+        Ok(table) => table,
+        Err(e) => {
+            visited.remove(&canonical);
+            return (Table::new(), OriginTable::new(), files, vec![e]);
+        }
+    };
+    let imports = table.remove(CONFIG_KEY_IMPORTS)
+        .and_then(|v| v.try_into::<Vec<String>>().ok())
+        .unwrap_or_default();
+
+    if let Err(e) = validate_table(&table) {
+        visited.remove(&canonical);
+        return (Table::new(), OriginTable::new(), files, vec![e]);
+    }
+
+    let parent_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut merged = Table::new();
+    let mut merged_origins = OriginTable::new();
+    let mut errors = Vec::new();
+    for import in &imports {
+        let import_path = resolve_import_path(parent_dir, import);
+        let (imported, imported_origins, mut imported_files, mut imported_errors) =
+            load_config_rec(&import_path, visited, depth + 1);
+        merge_tables(&mut merged, &imported);
+        merge_origins(&mut merged_origins, &imported_origins);
+        files.append(&mut imported_files);
+        errors.append(&mut imported_errors);
+    }
+    let own_origins = uniform_origins(&table, ConfigOrigin::UserFile(path.to_owned()));
+    merge_tables(&mut merged, &table);
+    merge_origins(&mut merged_origins, &own_origins);
+    visited.remove(&canonical);
+    (merged, merged_origins, files, errors)
+}
+
+/// Parses `path` as a single TOML config file, without resolving
+/// `imports` or validating against `SCHEMA`. A nonexistent `path`
+/// yields an empty table rather than an error, since "no user config
+/// yet" isn't something worth reporting to the client.
+fn load_config_raw(path: &Path) -> Result<Table, ConfigError> {
+    if !path.exists() {
+        return Ok(Table::new());
+    }
     let conf: config_rs::File<_> = path.into();
     conf.format(FileFormat::Toml)
         .collect()
-        .map_err(|e| eprintln!("Error reading config: {:?}", e))
+        .map_err(|e| ConfigError::Parse { path: path.to_owned(), message: e.to_string() })
+}
+
+/// Resolves an `imports` entry against the directory of the file that
+/// referenced it, expanding a leading `~`.
+fn resolve_import_path(base_dir: &Path, import: &str) -> PathBuf {
+    let expanded = expand_tilde(import);
+    if expanded.is_absolute() {
+        expanded
+    } else {
+        base_dir.join(expanded)
+    }
+}
+
+/// Expands a leading `~` to `$HOME`.
+fn expand_tilde(import: &str) -> PathBuf {
+    if import == "~" || import.starts_with("~/") {
+        if let Ok(home) = env::var("HOME") {
+            return PathBuf::from(import.replacen('~', &home, 1));
+        }
+    }
+    PathBuf::from(import)
+}
+
+/// Returns config overrides found in the current process's
+/// environment variables. See `env_overrides_impl`.
+fn env_overrides() -> (Table, OriginTable, Vec<ConfigError>) {
+    let vars = env::vars().collect();
+    env_overrides_impl(&vars)
+}
+
+/// Scans `vars` for `XI_CONFIG_`-prefixed entries (e.g.
+/// `XI_CONFIG_TAB_SIZE`), mapping each to the config key obtained by
+/// stripping the prefix and lowercasing (`tab_size`), and parses the
+/// value according to that key's schema type. An unknown key, or a
+/// value that doesn't parse as its schema type, is reported as a
+/// `ConfigError` and skipped.
+///
+/// `vars` is passed in explicitly, rather than read directly from the
+/// environment, for easier testing; see `config_dir_impl` for the same
+/// pattern.
+fn env_overrides_impl(vars: &HashMap<String, String>) -> (Table, OriginTable, Vec<ConfigError>) {
+    let mut table = Table::new();
+    let mut origins = OriginTable::new();
+    let mut errors = Vec::new();
+    for (var_name, raw_value) in vars.iter() {
+        if !var_name.starts_with(XI_CONFIG_ENV_PREFIX) { continue }
+        let key = var_name[XI_CONFIG_ENV_PREFIX.len()..].to_lowercase();
+        match SCHEMA.iter().find(|&&(name, _, _)| name == key) {
+            None => errors.push(ConfigError::EnvVar {
+                var: var_name.to_owned(),
+                message: format!("unknown config key {:?}", key),
+            }),
+            Some(&(name, ref value_type, _)) => {
+                match parse_env_value(value_type, raw_value) {
+                    Ok(value) => {
+                        table.insert(name.to_owned(), value);
+                        origins.insert(name.to_owned(), ConfigOrigin::EnvVar);
+                    }
+                    Err(e) => errors.push(ConfigError::EnvVar {
+                        var: var_name.to_owned(),
+                        message: e,
+                    }),
+                }
+            }
+        }
+    }
+    (table, origins, errors)
+}
+
+/// Parses a raw environment variable string into a `Value` of the
+/// shape described by `value_type`.
+fn parse_env_value(value_type: &ValueType, raw: &str) -> Result<Value, String> {
+    match *value_type {
+        ValueType::Bool => raw.parse::<bool>()
+            .map(Value::from)
+            .map_err(|e| e.to_string()),
+        ValueType::UInt => raw.parse::<u64>()
+            .map(Value::from)
+            .map_err(|e| e.to_string()),
+        ValueType::Str => Ok(Value::from(raw)),
+        ValueType::StrList => Ok(Value::from(raw.split(',')
+                                  .map(str::trim)
+                                  .map(String::from)
+                                  .collect::<Vec<_>>())),
+    }
 }
 
-/// Loads all of the syntax-specific config files in the target directory.
-fn load_syntax_configs(config_dir: &Path) -> HashMap<SyntaxDefinition, Table> {
+/// Loads all of the syntax-specific config files in the target
+/// directory, along with each one's config name and the files that
+/// contributed to it (for import tracking). If two files in the same
+/// directory resolve to the same syntax, an `AmbiguousSource` error is
+/// returned for the pair and the one visited first is kept.
+fn load_syntax_configs(config_dir: &Path)
+    -> (HashMap<SyntaxDefinition, (Table, OriginTable, String, Vec<PathBuf>, PathBuf)>, Vec<ConfigError>)
+{
     let contents = config_dir.read_dir()
         .map(|dir| {
             dir.flat_map(Result::ok)
@@ -328,6 +1031,7 @@ fn load_syntax_configs(config_dir: &Path) -> HashMap<SyntaxDefinition, Table> {
         .unwrap_or_default();
 
     let mut result = HashMap::new();
+    let mut errors = Vec::new();
     for config_path in contents {
         // config is invalid if path isn't utf-8; lossy gives better errors
         let file_name = config_path.file_name().unwrap().to_string_lossy();
@@ -335,18 +1039,25 @@ fn load_syntax_configs(config_dir: &Path) -> HashMap<SyntaxDefinition, Table> {
             continue
         }
 
-        let file_stem = config_path.file_stem().unwrap().to_string_lossy();
-        let syntax = SyntaxDefinition::try_from_name(&file_stem);
-        let conf = load_config(&config_path);
-        match (syntax, conf) {
-            (Some(s), Ok(c)) => { result.insert(s, c); }
-            (None, _) => eprintln!("unrecognized syntax name: {:?}",
-                                           &file_stem),
-            (_, Err(err)) => eprintln!("Error parsing config {:?}\n{:?}",
-                                        &config_path, err),
+        let file_stem = config_path.file_stem().unwrap().to_string_lossy().into_owned();
+        match SyntaxDefinition::try_from_name(&file_stem) {
+            Some(s) => {
+                if let Some(&(_, _, _, _, ref first)) = result.get(&s) {
+                    errors.push(ConfigError::AmbiguousSource {
+                        syntax: s,
+                        first: first.clone(),
+                        second: config_path,
+                    });
+                    continue;
+                }
+                let (table, origins, files, config_errors) = load_config(&config_path);
+                errors.extend(config_errors);
+                result.insert(s, (table, origins, file_stem, files, config_path));
+            }
+            None => errors.push(ConfigError::UnrecognizedSyntax(file_stem)),
         }
     }
-    result
+    (result, errors)
 }
 
 /// Returns the location of the active config directory.
@@ -383,6 +1094,13 @@ fn merge_tables(base: &mut Table, other: &Table) {
     }
 }
 
+/// Updates `base` with origins in `other`.
+fn merge_origins(base: &mut OriginTable, other: &OriginTable) {
+    for (k, v) in other.iter() {
+        base.insert(k.to_owned(), v.clone());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -425,13 +1143,18 @@ mod tests {
             .collect()
             .unwrap();
 
+        let rust_origins = uniform_origins(&rust_config, ConfigOrigin::UserFile(PathBuf::from("rust.xiconfig")));
         let mut user_syntax = HashMap::new();
-        user_syntax.insert(SyntaxDefinition::Rust, rust_config);
+        user_syntax.insert(SyntaxDefinition::Rust,
+                           (rust_config, rust_origins, "rust".to_owned(), Vec::new(),
+                            PathBuf::from("rust.xiconfig")));
 
+        let user_origins = uniform_origins(&user_config, ConfigOrigin::UserFile(PathBuf::from("preferences.xiconfig")));
         let mut manager = ConfigManager::default();
-        manager.set_user_configs(Some(user_config), Some(user_syntax));
+        manager.set_user_configs(Some((user_config, user_origins, Vec::new(), PathBuf::from("preferences.xiconfig"))),
+                                 Some(user_syntax));
         let buf_id = BufferIdentifier::new(1);
-        manager.set_override("tab_size", 67, buf_id.clone(), false);
+        manager.set_override("tab_size", 67, buf_id.clone(), false).unwrap();
 
         let config = manager.get_config(None, None);
         assert_eq!(config.tab_size, 42);
@@ -446,8 +1169,280 @@ mod tests {
         assert_eq!(config.tab_size, 67);
 
         // user override trumps everything
-        manager.set_override("tab_size", 85, buf_id.clone(), true);
+        manager.set_override("tab_size", 85, buf_id.clone(), true).unwrap();
         let config = manager.get_config(SyntaxDefinition::Rust, buf_id.clone());
         assert_eq!(config.tab_size, 85);
     }
+
+    #[test]
+    fn test_config_origins() {
+        let mut manager = ConfigManager::default();
+        let origins = manager.get_config_with_origins(None, None);
+        assert_eq!(origins.get("tab_size").map(|&(_, ref o)| o.clone()),
+                  Some(ConfigOrigin::Default));
+
+        let user_config = r#"tab_size = 42"#;
+        let user_config = config_rs::File::from_str(user_config, FileFormat::Toml)
+            .collect()
+            .unwrap();
+        let path = PathBuf::from("preferences.xiconfig");
+        let origins = uniform_origins(&user_config, ConfigOrigin::UserFile(path.clone()));
+        manager.set_user_configs(Some((user_config, origins, Vec::new(), path.clone())), None);
+        let origins = manager.get_config_with_origins(None, None);
+        assert_eq!(origins.get("tab_size").map(|&(_, ref o)| o.clone()),
+                  Some(ConfigOrigin::UserFile(path)));
+
+        let buf_id = BufferIdentifier::new(1);
+        manager.set_override("tab_size", 67, buf_id.clone(), true).unwrap();
+        let origins = manager.get_config_with_origins(None, buf_id);
+        assert_eq!(origins.get("tab_size").map(|&(_, ref o)| o.clone()),
+                  Some(ConfigOrigin::RpcOverride));
+    }
+
+    #[test]
+    fn test_schema_validation() {
+        let mut manager = ConfigManager::default();
+        let buf_id = BufferIdentifier::new(1);
+
+        let err = manager.set_override("not_a_real_setting", 1, buf_id.clone(), true)
+            .unwrap_err();
+        assert!(err.to_string().contains("unknown config key"));
+
+        let err = manager.set_override("tab_size", "not a number", buf_id.clone(), true)
+            .unwrap_err();
+        assert!(err.to_string().contains("expected <unsigned integer>"));
+
+        assert!(manager.set_override("tab_size", 8, buf_id.clone(), true).is_ok());
+    }
+
+    #[test]
+    fn test_available_settings() {
+        let manager = ConfigManager::default();
+        let settings = manager.available_settings();
+        assert!(settings.iter().any(|s| s.name == "tab_size" && s.value_type == ValueType::UInt));
+    }
+
+    #[test]
+    fn test_imports() {
+        use std::fs;
+
+        let dir = env::temp_dir().join("xi_config_test_imports");
+        let _ = fs::create_dir_all(&dir);
+
+        let base_path = dir.join("base.xiconfig");
+        fs::write(&base_path,
+                  "tab_size = 2\ntranslate_tabs_to_spaces = true").unwrap();
+
+        let main_path = dir.join("preferences.xiconfig");
+        fs::write(&main_path,
+                  "imports = [\"base.xiconfig\"]\ntab_size = 4").unwrap();
+
+        let (table, origins, files, errors) = load_config(&main_path);
+        assert_eq!(files.len(), 2);
+        assert!(errors.is_empty());
+        assert_eq!(origins.get("tab_size"),
+                  Some(&ConfigOrigin::UserFile(main_path.clone())));
+        assert_eq!(origins.get("translate_tabs_to_spaces"),
+                  Some(&ConfigOrigin::UserFile(base_path)));
+        // `table` holds only the keys the files set; layer it over the
+        // defaults so it converts into a complete `Config`.
+        let (mut settings, _) = defaults::platform_defaults();
+        merge_tables(&mut settings, &table);
+        let value: Value = settings.into();
+        let config: Config = value.try_into().unwrap();
+        assert_eq!(config.tab_size, 4);
+        assert_eq!(config.translate_tabs_to_spaces, true);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_import_cycle() {
+        use std::fs;
+
+        let dir = env::temp_dir().join("xi_config_test_import_cycle");
+        let _ = fs::create_dir_all(&dir);
+
+        let a_path = dir.join("a.xiconfig");
+        let b_path = dir.join("b.xiconfig");
+        fs::write(&a_path, "imports = [\"b.xiconfig\"]\ntab_size = 1").unwrap();
+        fs::write(&b_path, "imports = [\"a.xiconfig\"]\ntab_size = 2").unwrap();
+
+        // Should not recurse forever; the cycle is broken and loading
+        // completes with whatever could be resolved, reporting the
+        // cycle as a `ConfigError` rather than looping.
+        let (_table, _origins, _files, errors) = load_config(&a_path);
+        assert!(!errors.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_import_diamond() {
+        use std::fs;
+
+        let dir = env::temp_dir().join("xi_config_test_import_diamond");
+        let _ = fs::create_dir_all(&dir);
+
+        let base_path = dir.join("base.xiconfig");
+        let topic_a_path = dir.join("topic_a.xiconfig");
+        let topic_b_path = dir.join("topic_b.xiconfig");
+        let main_path = dir.join("preferences.xiconfig");
+        fs::write(&base_path, "tab_size = 2").unwrap();
+        fs::write(&topic_a_path, "imports = [\"base.xiconfig\"]").unwrap();
+        fs::write(&topic_b_path, "imports = [\"base.xiconfig\"]").unwrap();
+        fs::write(&main_path,
+                  "imports = [\"topic_a.xiconfig\", \"topic_b.xiconfig\"]").unwrap();
+
+        // Two sibling imports sharing a common base is not a cycle;
+        // both branches should resolve `base.xiconfig` cleanly.
+        let (table, _origins, _files, errors) = load_config(&main_path);
+        assert!(errors.is_empty());
+        let (mut settings, _) = defaults::platform_defaults();
+        merge_tables(&mut settings, &table);
+        let value: Value = settings.into();
+        let config: Config = value.try_into().unwrap();
+        assert_eq!(config.tab_size, 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_env_overrides_impl() {
+        let mut vars = HashMap::new();
+        vars.insert("XI_CONFIG_TAB_SIZE".to_owned(), "9".to_owned());
+        vars.insert("XI_CONFIG_TRANSLATE_TABS_TO_SPACES".to_owned(), "true".to_owned());
+        vars.insert("XI_CONFIG_NOT_A_SETTING".to_owned(), "1".to_owned());
+        vars.insert("UNRELATED_VAR".to_owned(), "1".to_owned());
+        vars.insert("XI_CONFIG_TAB_SIZE_NOT_A_NUMBER".to_owned(), "nope".to_owned());
+
+        let (table, origins, errors) = env_overrides_impl(&vars);
+        assert_eq!(table.len(), 2);
+        assert_eq!(origins.get("tab_size"), Some(&ConfigOrigin::EnvVar));
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_env_overrides_precedence() {
+        let mut manager = ConfigManager::default();
+        let user_config = r#"tab_size = 42"#;
+        let user_config = config_rs::File::from_str(user_config, FileFormat::Toml)
+            .collect()
+            .unwrap();
+        let origins = uniform_origins(&user_config, ConfigOrigin::UserFile(PathBuf::from("preferences.xiconfig")));
+        manager.set_user_configs(
+            Some((user_config, origins, Vec::new(), PathBuf::from("preferences.xiconfig"))), None);
+
+        let mut vars = HashMap::new();
+        vars.insert("XI_CONFIG_TAB_SIZE".to_owned(), "7".to_owned());
+        let (env_overrides, env_origins, _errors) = env_overrides_impl(&vars);
+        manager.env_overrides = env_overrides;
+        manager.env_origins = env_origins;
+
+        // the env var beats the user file...
+        let config = manager.get_config(None, None);
+        assert_eq!(config.tab_size, 7);
+
+        // ...but a buffer override still wins over the env var.
+        let buf_id = BufferIdentifier::new(1);
+        manager.set_override("tab_size", 99, buf_id.clone(), false).unwrap();
+        let config = manager.get_config(None, buf_id);
+        assert_eq!(config.tab_size, 99);
+    }
+
+    #[test]
+    fn test_reload_keeps_previous_config_on_error() {
+        use std::fs;
+
+        let dir = env::temp_dir().join("xi_config_test_reload_error");
+        let _ = fs::create_dir_all(&dir);
+        let pref_path = dir.join(XI_CONFIG_FILE_NAME);
+        fs::write(&pref_path, "tab_size = 8").unwrap();
+
+        let mut manager = ConfigManager::default();
+        manager.set_config_dir(&dir);
+        assert_eq!(manager.get_config(None, None).tab_size, 8);
+        assert!(manager.take_errors().is_empty());
+
+        // a typo (invalid TOML) in a live-reloaded file should not
+        // corrupt the running session.
+        fs::write(&pref_path, "tab_size = [[[").unwrap();
+        manager.reload_for_path(&pref_path);
+        assert_eq!(manager.get_config(None, None).tab_size, 8);
+        assert!(!manager.take_errors().is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_local_config_discovery() {
+        use std::fs;
+
+        let root = env::temp_dir().join("xi_config_test_local_discovery");
+        let project = root.join("project");
+        let nested = project.join("src");
+        let _ = fs::create_dir_all(&nested);
+
+        fs::write(root.join(XI_CONFIG_FILE_NAME), "tab_size = 2").unwrap();
+        fs::write(project.join(XI_CONFIG_FILE_NAME), "tab_size = 8").unwrap();
+
+        let mut manager = ConfigManager::default();
+        let buf_id = BufferIdentifier::new(1);
+        manager.set_buffer_path(buf_id.clone(), nested.join("main.rs"));
+
+        // the closer ancestor (`project/`) wins over the farther one
+        // (`root/`).
+        let config = manager.get_config(None, buf_id.clone());
+        assert_eq!(config.tab_size, 8);
+        let origins = manager.get_config_with_origins(None, buf_id.clone());
+        assert_eq!(origins.get("tab_size").map(|&(_, ref o)| o.clone()),
+                  Some(ConfigOrigin::UserFile(project.join(XI_CONFIG_FILE_NAME))));
+
+        // a buffer with no local config falls back to the global
+        // default.
+        let other_buf_id = BufferIdentifier::new(2);
+        let config = manager.get_config(None, other_buf_id);
+        assert_eq!(config.tab_size, 4);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_local_config_discovery_ignores_preferences_as_syntax() {
+        use std::fs;
+
+        // every ancestor dir that has `preferences.xiconfig` is exactly
+        // the case this feature discovers; `load_syntax_configs` must
+        // not treat that reserved name as an (unrecognized) syntax.
+        let root = env::temp_dir().join("xi_config_test_discovery_no_syntax_noise");
+        let _ = fs::create_dir_all(&root);
+        fs::write(root.join(XI_CONFIG_FILE_NAME), "tab_size = 2").unwrap();
+
+        let (syntax_configs, errors) = load_syntax_configs(&root);
+        assert!(syntax_configs.is_empty());
+        assert!(errors.is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_local_config_reload() {
+        use std::fs;
+
+        let dir = env::temp_dir().join("xi_config_test_local_reload");
+        let _ = fs::create_dir_all(&dir);
+        let pref_path = dir.join(XI_CONFIG_FILE_NAME);
+        fs::write(&pref_path, "tab_size = 8").unwrap();
+
+        let mut manager = ConfigManager::default();
+        let buf_id = BufferIdentifier::new(1);
+        manager.set_buffer_path(buf_id.clone(), dir.join("main.rs"));
+        assert_eq!(manager.get_config(None, buf_id.clone()).tab_size, 8);
+
+        fs::write(&pref_path, "tab_size = 16").unwrap();
+        manager.reload_for_path(&pref_path);
+        assert_eq!(manager.get_config(None, buf_id).tab_size, 16);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
 }